@@ -0,0 +1,999 @@
+use bitvec::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+/// A fixed salt XOR-ed into the second base hash so that `h2` differs from
+/// `h1` even though both come from the same single hash of the item.
+const DOUBLE_HASH_SALT: u64 = 0x9e3779b97f4a7c15;
+
+/// Reserves the top byte of a precomputed hash for caller metadata; only
+/// the masked low bits participate in slot derivation. Callers packing a
+/// precomputed hash alongside other data in a single `u64` should mask
+/// with this before calling `insert_hash`/`contains_hash`.
+pub const BLOOM_HASH_MASK: u64 = u64::MAX >> 8;
+
+/// Wire format version for [`BloomFilter::to_bytes`]. Bump this and branch
+/// on it in `from_bytes` if the layout below ever changes.
+const BLOOM_FORMAT_VERSION: u8 = 1;
+
+/// Magic bytes a serialized `BloomFilter` starts with, so `from_bytes` can
+/// reject unrelated data before it even looks at the header fields.
+const BLOOM_MAGIC: [u8; 4] = *b"BLMF";
+
+/// Why [`BloomFilter::from_bytes`] rejected a payload.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BloomFilterDecodeError {
+    /// The payload ended before a header field or the full bit array
+    /// could be read.
+    Truncated,
+    /// The first four bytes weren't `BLOOM_MAGIC`.
+    BadMagic,
+    /// The version byte doesn't match `BLOOM_FORMAT_VERSION`.
+    UnsupportedVersion(u8),
+    /// The sizing-mode tag byte wasn't a recognized variant.
+    InvalidSizingMode(u8),
+    /// The declared bit length doesn't fit in the words that followed it.
+    InconsistentBitLength,
+}
+
+impl std::fmt::Display for BloomFilterDecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BloomFilterDecodeError::Truncated => write!(f, "payload truncated"),
+            BloomFilterDecodeError::BadMagic => write!(f, "not a BloomFilter payload"),
+            BloomFilterDecodeError::UnsupportedVersion(v) => {
+                write!(f, "unsupported format version {v}")
+            }
+            BloomFilterDecodeError::InvalidSizingMode(tag) => {
+                write!(f, "invalid sizing mode tag {tag}")
+            }
+            BloomFilterDecodeError::InconsistentBitLength => {
+                write!(f, "bit array length inconsistent with word count")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BloomFilterDecodeError {}
+
+/// A minimal cursor over a serialized filter's bytes, used only by
+/// `BloomFilter::from_bytes`. Every read checks the remaining length so a
+/// truncated or corrupt payload fails with `Truncated` instead of panicking.
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        ByteReader { bytes, pos: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], BloomFilterDecodeError> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .ok_or(BloomFilterDecodeError::Truncated)?;
+        let slice = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or(BloomFilterDecodeError::Truncated)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, BloomFilterDecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, BloomFilterDecodeError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, BloomFilterDecodeError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> Result<f64, BloomFilterDecodeError> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+/// Hashes `item` once with the standard library's default hasher.
+fn hash_of<T: Hash>(item: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    item.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Derives the two base hashes `(h1, h2)` that the "less hashing, same
+/// performance" (Kirsch-Mitzenmacher) scheme derives all `k` probe indices
+/// from, instead of running `k` independent hash functions.
+fn probe_hashes(hash: u64) -> (u64, u64) {
+    let h1 = hash & BLOOM_HASH_MASK;
+    let h2 = h1 ^ DOUBLE_HASH_SALT;
+    (h1, h2)
+}
+
+/// Derives the `i`th of `hash_count` probe indices from two base hashes
+/// via double hashing (`g_i = h1 + i * h2`), folded down by `sizing`.
+/// Shared by `BloomFilter` and `CountingBloomFilter` so both probe their
+/// backing storage identically.
+fn probe_slot_index(sizing: &Sizing, h1: u64, h2: u64, i: usize) -> usize {
+    let combined = h1.wrapping_add((i as u64).wrapping_mul(h2));
+    sizing.index(combined)
+}
+
+/// Controls how a combined probe hash is folded down into a slot index.
+///
+/// `Modulo` supports an arbitrary bit-array size but costs a division per
+/// probe. `PowerOfTwo` rounds the size up to the next power of two and
+/// replaces that division with a mask, first XOR-folding the `shift` bits
+/// just above the mask (`hash >> shift`) down into it so those bits aren't
+/// simply discarded. Bits beyond `2 * shift` still do not reach the index.
+enum Sizing {
+    Modulo(usize),
+    PowerOfTwo { mask: u64, shift: u32 },
+}
+
+impl Sizing {
+    fn power_of_two(size: usize) -> Self {
+        let bits = size.max(1).next_power_of_two();
+        Sizing::PowerOfTwo {
+            mask: (bits - 1) as u64,
+            shift: bits.trailing_zeros(),
+        }
+    }
+
+    fn bits(&self) -> usize {
+        match self {
+            Sizing::Modulo(size) => *size,
+            Sizing::PowerOfTwo { mask, .. } => *mask as usize + 1,
+        }
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        match *self {
+            Sizing::Modulo(size) => (hash % size as u64) as usize,
+            Sizing::PowerOfTwo { mask, shift } => ((hash ^ (hash >> shift)) & mask) as usize,
+        }
+    }
+}
+
+pub struct BloomFilter<T: Hash + Clone> {
+    bit_vector: BitVec,
+    sizing: Sizing,
+    hash_count: usize,
+    count: usize,
+    /// The FPR this filter resizes to stay under. `None` means resizing is
+    /// entirely the caller's responsibility (the `with_hash_count*`
+    /// constructors), in which case `items` stays empty and unused.
+    target_fpr: Option<f64>,
+    /// Only populated when `target_fpr` is set: a resize needs the raw
+    /// items to replay through `add` into the larger bit array, since a
+    /// set bit can't be un-hashed back into an index for the new size.
+    items: Vec<T>,
+    /// Whether `items` is guaranteed to hold every item counted in
+    /// `count`. True for filters built through the constructors below;
+    /// false for a filter produced by `from_bytes` that already had items
+    /// added before it was serialized, since `to_bytes` doesn't persist
+    /// `items`. `should_resize` checks this so a resize never discards
+    /// membership for items it can't replay — see `from_bytes`.
+    replay_complete: bool,
+}
+
+impl<T: Hash + Clone> BloomFilter<T> {
+    fn from_sizing(sizing: Sizing, hash_count: usize, target_fpr: Option<f64>) -> Self {
+        let bits = sizing.bits();
+        BloomFilter {
+            bit_vector: bitvec![0; bits],
+            sizing,
+            hash_count,
+            count: 0,
+            target_fpr,
+            items: Vec::new(),
+            replay_complete: true,
+        }
+    }
+
+    /// Builds a filter of `size` bits using `hash_count` probes per item,
+    /// synthesized from two base hashes via double hashing rather than
+    /// `hash_count` separate hash functions. Never resizes automatically;
+    /// use `with_target` for that.
+    pub fn with_hash_count(size: usize, hash_count: usize) -> Self {
+        Self::from_sizing(Sizing::Modulo(size), hash_count, None)
+    }
+
+    /// Like `with_hash_count`, but rounds `size` up to the next power of
+    /// two and replaces the per-probe modulo with a bit mask, trading
+    /// precise sizing for a measurably faster hot path.
+    pub fn with_hash_count_pow2(size: usize, hash_count: usize) -> Self {
+        Self::from_sizing(Sizing::power_of_two(size), hash_count, None)
+    }
+
+    /// Builds a filter sized for `expected_items` items at a `target_fpr`
+    /// false-positive rate, using the standard sizing formulas:
+    ///
+    /// - bits: `m = ceil(-n * ln(p) / (ln 2)^2)`
+    /// - hash functions: `k = max(1, round((m / n) * ln 2))`
+    ///
+    /// Unlike `with_hash_count`, the resulting filter tracks its projected
+    /// (analytic) false-positive rate and grows itself, geometrically, to stay under
+    /// `target_fpr` as more items than `expected_items` are added.
+    ///
+    /// The actual bit-array size is `m` rounded up to the next power of
+    /// two (not `m` exactly) so the geometric resize above can keep using
+    /// power-of-two sizing; this only ever gives a lower FPR than
+    /// requested, never a higher one, but means `size()` can be up to 2x
+    /// the `m` the formula above computes.
+    pub fn with_target(expected_items: usize, target_fpr: f64) -> Self {
+        let n = expected_items.max(1) as f64;
+        let m = (-n * target_fpr.ln() / std::f64::consts::LN_2.powi(2)).ceil() as usize;
+        let k = (((m as f64 / n) * std::f64::consts::LN_2).round() as usize).max(1);
+
+        Self::from_sizing(Sizing::power_of_two(m.max(1)), k, Some(target_fpr))
+    }
+
+    pub fn size(&self) -> usize {
+        self.sizing.bits()
+    }
+
+    /// Derives the `i`th of `hash_count` probe indices from the two base
+    /// hashes: `g_i = (h1 + i * h2)`, folded down by the active `Sizing`.
+    fn slot_index(&self, h1: u64, h2: u64, i: usize) -> usize {
+        probe_slot_index(&self.sizing, h1, h2, i)
+    }
+
+    /// Sets an item's `k` slots from a precomputed hash, skipping the
+    /// `T: Hash` step entirely. Only the bits covered by `BLOOM_HASH_MASK`
+    /// participate in slot derivation.
+    pub fn insert_hash(&mut self, hash: u64) {
+        let (h1, h2) = probe_hashes(hash);
+        for i in 0..self.hash_count {
+            let index = self.slot_index(h1, h2, i);
+            self.bit_vector.set(index, true);
+        }
+    }
+
+    /// Tests a precomputed hash against an item's `k` slots. See
+    /// `insert_hash`.
+    pub fn contains_hash(&self, hash: u64) -> bool {
+        let (h1, h2) = probe_hashes(hash);
+        (0..self.hash_count).all(|i| self.bit_vector[self.slot_index(h1, h2, i)])
+    }
+
+    pub fn add(&mut self, item: &T) {
+        if self.should_resize() {
+            self.resize();
+        }
+
+        self.insert_hash(hash_of(item));
+        self.count += 1;
+        // A filter whose replay buffer wasn't restored by `from_bytes`
+        // never resizes again (see `should_resize`), so there's no point
+        // growing `items` forever for a buffer that will never be used.
+        if self.target_fpr.is_some() && self.replay_complete {
+            self.items.push(item.clone());
+        }
+    }
+
+    pub fn contains(&self, item: &T) -> bool {
+        self.contains_hash(hash_of(item))
+    }
+
+    /// The analytic false-positive rate for `n` items at the filter's
+    /// current `size`/`hash_count`, from the standard Bloom filter formula.
+    fn projected_false_positive_rate(&self, n: usize) -> f64 {
+        let k = self.hash_count as f64;
+        let m = self.size() as f64;
+        let n = n as f64;
+
+        (1.0 - (-k * n / m).exp()).powf(k)
+    }
+
+    pub fn calculate_false_positive_rate(&self) -> f64 {
+        self.projected_false_positive_rate(self.count)
+    }
+
+    /// Resizes when the next insertion would push the projected FPR past
+    /// `target_fpr`. Filters with no target never resize automatically,
+    /// and neither does a filter whose `items` replay buffer isn't a
+    /// complete record of what's been added (see `replay_complete`) —
+    /// growing it would require replaying items it no longer has, which
+    /// would silently turn their membership into a false negative.
+    fn should_resize(&self) -> bool {
+        match self.target_fpr {
+            Some(target) if self.replay_complete => {
+                self.projected_false_positive_rate(self.count + 1) > target
+            }
+            _ => false,
+        }
+    }
+
+    /// Grows geometrically to the smallest power-of-two size whose
+    /// predicted FPR at `count + 1` items is back under `target_fpr`.
+    fn resize(&mut self) {
+        let target = self
+            .target_fpr
+            .expect("resize is only called when target_fpr is set");
+        let old_items = self.items.clone();
+        let n = (self.count + 1) as f64;
+        let k = self.hash_count as f64;
+
+        let mut bits = self.size().next_power_of_two() * 2;
+        while (1.0 - (-k * n / bits as f64).exp()).powf(k) > target {
+            bits *= 2;
+        }
+
+        self.sizing = Sizing::power_of_two(bits);
+        self.bit_vector = bitvec![0; self.sizing.bits()];
+        self.count = 0;
+        self.items.clear();
+
+        for item in old_items {
+            self.add(&item);
+        }
+    }
+
+    /// The sizing-mode tag and its single parameter, as written by
+    /// `to_bytes`: `(0, size)` for `Modulo`, `(1, mask)` for `PowerOfTwo`
+    /// (`shift` is recomputed from `mask` on decode, so it isn't stored).
+    fn sizing_tag_and_param(&self) -> (u8, u64) {
+        match self.sizing {
+            Sizing::Modulo(size) => (0, size as u64),
+            Sizing::PowerOfTwo { mask, .. } => (1, mask),
+        }
+    }
+
+    /// Serializes this filter to a compact binary format: a header (magic,
+    /// version, sizing mode, hash count, item count, target FPR) followed
+    /// by the bit array's raw words, per `BitVec::as_raw_slice`, rather than
+    /// one byte per bit.
+    ///
+    /// Only the structural state needed to keep using the filter is
+    /// serialized — the `items` replay buffer `with_target` filters use
+    /// internally is not. A filter with items already added before being
+    /// serialized therefore comes back from `from_bytes` unable to grow:
+    /// `should_resize` sees its replay buffer is incomplete and leaves
+    /// automatic resizing off rather than risk a resize discarding those
+    /// items' membership (a false negative). Its contents stay intact and
+    /// queryable either way; it just stops tracking `target_fpr` and keeps
+    /// whatever size it was serialized at.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&BLOOM_MAGIC);
+        out.push(BLOOM_FORMAT_VERSION);
+
+        let (sizing_tag, sizing_param) = self.sizing_tag_and_param();
+        out.push(sizing_tag);
+        out.extend_from_slice(&sizing_param.to_le_bytes());
+
+        out.extend_from_slice(&(self.hash_count as u32).to_le_bytes());
+        out.extend_from_slice(&(self.count as u64).to_le_bytes());
+
+        match self.target_fpr {
+            Some(fpr) => {
+                out.push(1);
+                out.extend_from_slice(&fpr.to_le_bytes());
+            }
+            None => out.push(0),
+        }
+
+        let raw = self.bit_vector.as_raw_slice();
+        out.extend_from_slice(&(self.bit_vector.len() as u64).to_le_bytes());
+        out.extend_from_slice(&(raw.len() as u64).to_le_bytes());
+        for word in raw {
+            out.extend_from_slice(&(*word as u64).to_le_bytes());
+        }
+
+        out
+    }
+
+    /// Reconstructs a filter written by `to_bytes`, re-deriving its probing
+    /// from the stored sizing/hash-count rather than any closures (the
+    /// double-hashing scheme needs none). Rejects a payload with a
+    /// mismatched magic/version, an unrecognized sizing-mode tag, or fewer
+    /// bytes than the header declares, rather than silently truncating or
+    /// zero-filling the bit array.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BloomFilterDecodeError> {
+        let mut reader = ByteReader::new(bytes);
+
+        if reader.take(4)? != BLOOM_MAGIC.as_slice() {
+            return Err(BloomFilterDecodeError::BadMagic);
+        }
+
+        let version = reader.u8()?;
+        if version != BLOOM_FORMAT_VERSION {
+            return Err(BloomFilterDecodeError::UnsupportedVersion(version));
+        }
+
+        let sizing_tag = reader.u8()?;
+        let sizing_param = reader.u64()?;
+        let sizing = match sizing_tag {
+            0 => {
+                let size = sizing_param as usize;
+                if size == 0 {
+                    return Err(BloomFilterDecodeError::InconsistentBitLength);
+                }
+                Sizing::Modulo(size)
+            }
+            1 => {
+                // A valid mask is `bits - 1` for some power-of-two `bits`,
+                // so `mask + 1` must neither overflow (an attacker-chosen
+                // `u64::MAX` would panic here) nor land on a non-power-of-two.
+                let bits = sizing_param
+                    .checked_add(1)
+                    .filter(|bits| bits.is_power_of_two())
+                    .ok_or(BloomFilterDecodeError::InconsistentBitLength)?;
+                Sizing::PowerOfTwo {
+                    mask: sizing_param,
+                    shift: bits.trailing_zeros(),
+                }
+            }
+            tag => return Err(BloomFilterDecodeError::InvalidSizingMode(tag)),
+        };
+
+        let hash_count = reader.u32()? as usize;
+        let count = reader.u64()? as usize;
+
+        let target_fpr = match reader.u8()? {
+            1 => Some(reader.f64()?),
+            _ => None,
+        };
+
+        let bit_len = reader.u64()? as usize;
+        let word_count = reader.u64()? as usize;
+        // Don't pre-reserve capacity for an attacker/corruption-controlled
+        // `word_count` before it's been checked against the bytes actually
+        // available: `reader.u64()` below is itself bounds-checked, so a
+        // truncated or malicious payload fails with `Truncated` instead of
+        // attempting a multi-gigabyte allocation up front.
+        let mut words = Vec::new();
+        for _ in 0..word_count {
+            words.push(reader.u64()? as usize);
+        }
+
+        let mut bit_vector: BitVec = BitVec::from_vec(words);
+        if bit_len > bit_vector.len() {
+            return Err(BloomFilterDecodeError::InconsistentBitLength);
+        }
+        bit_vector.truncate(bit_len);
+
+        // `bit_len` only bounds the raw words against each other; it says
+        // nothing about whether the decoded `sizing` actually addresses
+        // this many bits. A mismatched mask/size would otherwise decode
+        // successfully and panic on the first out-of-bounds `slot_index`
+        // lookup in `contains`/`insert_hash`.
+        if sizing.bits() != bit_vector.len() {
+            return Err(BloomFilterDecodeError::InconsistentBitLength);
+        }
+
+        Ok(BloomFilter {
+            bit_vector,
+            sizing,
+            hash_count,
+            count,
+            target_fpr,
+            items: Vec::new(),
+            // `to_bytes` doesn't persist `items` (see its doc comment), so
+            // this filter's replay buffer is incomplete unless it never
+            // held anything in the first place. `should_resize` relies on
+            // this to never drop a deserialized item's membership.
+            replay_complete: count == 0,
+        })
+    }
+}
+
+/// A single slot in a `CountingBloomFilter`. Unlike the plain bit used by
+/// `BloomFilter`, a `Counter` can be decremented, which is what makes
+/// `remove` safe: for a multi-bit counter that has not saturated, a slot
+/// only reads as empty once every item that bumped it has also been
+/// removed. `Bit` clears its slot on a single decrement.
+pub trait Counter: Copy {
+    const ZERO: Self;
+
+    /// Bumps the counter by one, saturating at the counter's maximum value.
+    fn increment(self) -> Self;
+
+    /// Decrements the counter by one, refusing to go below zero.
+    fn decrement(self) -> Self;
+
+    fn is_nonzero(self) -> bool;
+}
+
+impl Counter for u8 {
+    const ZERO: Self = 0;
+
+    fn increment(self) -> Self {
+        self.saturating_add(1)
+    }
+
+    fn decrement(self) -> Self {
+        self.saturating_sub(1)
+    }
+
+    fn is_nonzero(self) -> bool {
+        self != 0
+    }
+}
+
+/// A single-bit counter that saturates at 1, recovering the exact
+/// set-only behavior of `BloomFilter`. This is the degenerate case
+/// `CountingBloomFilter` reduces to when no counting is needed.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct Bit(bool);
+
+impl Counter for Bit {
+    const ZERO: Self = Bit(false);
+
+    fn increment(self) -> Self {
+        Bit(true)
+    }
+
+    fn decrement(self) -> Self {
+        Bit(false)
+    }
+
+    fn is_nonzero(self) -> bool {
+        self.0
+    }
+}
+
+/// A Bloom filter that supports removal by replacing each bit with a small
+/// saturating counter `C` (e.g. `u8` for an 8-bit counting filter, or `Bit`
+/// to recover the exact behavior of `BloomFilter`). Probes its `hash_count`
+/// slots the same way `BloomFilter` does — two base hashes synthesized into
+/// `hash_count` indices via double hashing (`probe_hashes`/`probe_slot_index`),
+/// folded down by the same `Sizing` modulo/power-of-two modes — rather than
+/// a separate hash function per slot.
+///
+/// `add` increments an item's `k` slots; `remove` decrements them.
+/// `contains` is true only when every one of an item's slots is nonzero.
+pub struct CountingBloomFilter<T: Hash, C: Counter = Bit> {
+    counters: Vec<C>,
+    sizing: Sizing,
+    hash_count: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<T: Hash, C: Counter> CountingBloomFilter<T, C> {
+    fn from_sizing(sizing: Sizing, hash_count: usize) -> Self {
+        let bits = sizing.bits();
+        CountingBloomFilter {
+            counters: vec![C::ZERO; bits],
+            sizing,
+            hash_count,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Builds a filter of `size` counters using `hash_count` probes per
+    /// item, synthesized via double hashing. See `BloomFilter::with_hash_count`.
+    pub fn with_hash_count(size: usize, hash_count: usize) -> Self {
+        Self::from_sizing(Sizing::Modulo(size), hash_count)
+    }
+
+    /// Like `with_hash_count`, but rounds `size` up to the next power of
+    /// two and replaces the per-probe modulo with a bit mask. See
+    /// `BloomFilter::with_hash_count_pow2`.
+    pub fn with_hash_count_pow2(size: usize, hash_count: usize) -> Self {
+        Self::from_sizing(Sizing::power_of_two(size), hash_count)
+    }
+
+    pub fn size(&self) -> usize {
+        self.sizing.bits()
+    }
+
+    fn slot_index(&self, h1: u64, h2: u64, i: usize) -> usize {
+        probe_slot_index(&self.sizing, h1, h2, i)
+    }
+
+    pub fn add(&mut self, item: &T) {
+        let (h1, h2) = probe_hashes(hash_of(item));
+        for i in 0..self.hash_count {
+            let index = self.slot_index(h1, h2, i);
+            self.counters[index] = self.counters[index].increment();
+        }
+    }
+
+    /// Removes an item previously passed to `add`. Removing an item that
+    /// was never added (or removing it more times than it was added) is a
+    /// logic error that can cause false negatives for other items sharing
+    /// its slots, but it cannot corrupt a counter below zero.
+    pub fn remove(&mut self, item: &T) {
+        let (h1, h2) = probe_hashes(hash_of(item));
+        for i in 0..self.hash_count {
+            let index = self.slot_index(h1, h2, i);
+            self.counters[index] = self.counters[index].decrement();
+        }
+    }
+
+    pub fn contains(&self, item: &T) -> bool {
+        let (h1, h2) = probe_hashes(hash_of(item));
+        (0..self.hash_count).all(|i| self.counters[self.slot_index(h1, h2, i)].is_nonzero())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_bloom_filter() -> BloomFilter<String> {
+        let size = 10000;
+        let hash_count = 3;
+
+        BloomFilter::with_hash_count(size, hash_count)
+    }
+
+
+
+
+#[test]
+fn test_add_and_query() {
+    let mut filter = setup_bloom_filter();
+    let items = ["item1", "item2", "item3"];
+
+    for &item in &items {
+        filter.add(&item.to_string());
+        assert!(filter.contains(&item.to_string()), "Item added should be present.");
+    }
+
+    assert!(!filter.contains(&"nonexistent".to_string()), "Item not added should not be present.");
+}
+
+#[test]
+fn test_false_positive_rate_with_trials() {
+    let mut average_rate = 0.0;
+    let trials = 10;
+    for _ in 0..trials {
+        let mut filter = setup_bloom_filter();
+        let mut false_positives = 0;
+        let total_checks = 10000;
+
+        for i in 0..500 {
+            filter.add(&format!("item{}", i));
+        }
+
+        for i in 500..total_checks {
+            if filter.contains(&format!("item{}", i)) {
+                false_positives += 1;
+            }
+        }
+
+        let false_positive_rate = false_positives as f64 / (total_checks - 500) as f64;
+        average_rate += false_positive_rate / trials as f64;
+    }
+
+    println!("Average false positive rate over {} trials: {}", trials, average_rate);
+
+    let calculated_rate = setup_bloom_filter().calculate_false_positive_rate();
+    let tolerance = 0.05; // Adjusted tolerance
+    assert!(
+        average_rate <= calculated_rate + tolerance,
+        "Average false positive rate should be within tolerance of the calculated rate."
+    );
+}
+
+
+#[test]
+fn test_resize() {
+    // Only a filter built with a target FPR resizes itself; ask for very
+    // few expected items so real usage quickly pushes past the target.
+    let mut filter: BloomFilter<String> = BloomFilter::with_target(10, 0.01);
+    let initial_size = filter.size();
+
+    for i in 0..500 {
+        filter.add(&format!("item{}", i));
+    }
+
+    assert!(
+        filter.size() > initial_size,
+        "Filter should resize to a larger size once the projected FPR exceeds the target."
+    );
+
+    // Check that items added before resizing are still reported as present.
+    for i in 0..500 {
+        assert!(
+            filter.contains(&format!("item{}", i)),
+            "Items added before resizing should still be present."
+        );
+    }
+}
+
+#[test]
+fn test_with_hash_count_never_resizes() {
+    let mut filter: BloomFilter<String> = BloomFilter::with_hash_count(10, 3);
+    let initial_size = filter.size();
+
+    for i in 0..1000 {
+        filter.add(&format!("item{}", i));
+    }
+
+    assert_eq!(
+        filter.size(),
+        initial_size,
+        "A filter with no target FPR should never resize itself."
+    );
+}
+
+#[test]
+fn test_empty_filter() {
+    let filter = setup_bloom_filter();
+    let non_existent_items = ["ghost1", "ghost2", "ghost3"];
+
+    for &item in &non_existent_items {
+        assert!(
+            !filter.contains(&item.to_string()),
+            "Empty filter should not contain any items."
+        );
+    }
+}
+
+#[test]
+fn test_with_target_meets_requested_false_positive_rate() {
+    let expected_items = 1_000;
+    let target_fpr = 0.01;
+    let mut filter: BloomFilter<String> = BloomFilter::with_target(expected_items, target_fpr);
+
+    for i in 0..expected_items {
+        filter.add(&format!("item{}", i));
+    }
+
+    assert!(
+        filter.calculate_false_positive_rate() <= target_fpr,
+        "Filter sized via with_target should meet its target FPR at the expected item count."
+    );
+}
+
+#[test]
+fn test_pow2_sizing_rounds_up_and_matches_behavior() {
+    let mut filter: BloomFilter<String> = BloomFilter::with_hash_count_pow2(1000, 3);
+
+    assert!(filter.size().is_power_of_two(), "pow2 sizing should round up to a power of two.");
+    assert!(filter.size() >= 1000);
+
+    filter.add(&"item1".to_string());
+    assert!(filter.contains(&"item1".to_string()));
+    assert!(!filter.contains(&"nonexistent".to_string()));
+}
+
+#[test]
+fn test_insert_hash_and_contains_hash_agree_with_add_and_contains() {
+    let mut filter: BloomFilter<String> = BloomFilter::with_hash_count(10000, 3);
+    let hash = hash_of(&"item1".to_string());
+
+    filter.insert_hash(hash);
+
+    assert!(filter.contains_hash(hash), "A hash just inserted should be reported present.");
+    assert!(
+        filter.contains(&"item1".to_string()),
+        "insert_hash should set the same slots add() would for the same item."
+    );
+}
+
+#[test]
+fn test_to_bytes_from_bytes_round_trip() {
+    let mut filter: BloomFilter<String> = BloomFilter::with_target(100, 0.01);
+    for i in 0..50 {
+        filter.add(&format!("item{}", i));
+    }
+
+    let bytes = filter.to_bytes();
+    let restored: BloomFilter<String> =
+        BloomFilter::from_bytes(&bytes).expect("a freshly serialized filter should decode");
+
+    assert_eq!(restored.size(), filter.size());
+    for i in 0..50 {
+        assert!(
+            restored.contains(&format!("item{}", i)),
+            "items present before serializing should still be present after deserializing."
+        );
+    }
+    assert!(!restored.contains(&"never-added".to_string()));
+}
+
+#[test]
+fn test_resize_after_from_bytes_never_drops_prior_membership() {
+    // A loose target with a small expected count so a handful more inserts
+    // would normally force a resize right away.
+    let mut filter: BloomFilter<String> = BloomFilter::with_target(10, 0.5);
+    let originals: Vec<String> = (0..5).map(|i| format!("item{}", i)).collect();
+    for item in &originals {
+        filter.add(item);
+    }
+
+    let bytes = filter.to_bytes();
+    let mut restored: BloomFilter<String> =
+        BloomFilter::from_bytes(&bytes).expect("a freshly serialized filter should decode");
+
+    // Push well past the point that would trigger a resize if one fired.
+    for i in 0..200 {
+        restored.add(&format!("new-item{}", i));
+    }
+
+    for item in &originals {
+        assert!(
+            restored.contains(item),
+            "an item added before serializing must never become a false negative, \
+             even if further inserts after from_bytes would otherwise have resized."
+        );
+    }
+}
+
+#[test]
+fn test_add_does_not_accumulate_items_once_replay_is_impossible() {
+    let mut filter: BloomFilter<String> = BloomFilter::with_target(10, 0.5);
+    filter.add(&"item0".to_string());
+
+    let bytes = filter.to_bytes();
+    let mut restored: BloomFilter<String> =
+        BloomFilter::from_bytes(&bytes).expect("a freshly serialized filter should decode");
+    assert!(!restored.replay_complete);
+
+    for i in 0..1000 {
+        restored.add(&format!("new-item{}", i));
+    }
+
+    assert!(
+        restored.items.is_empty(),
+        "a filter that can never resize again (replay_complete == false) shouldn't keep \
+         cloning every added item into a buffer it will never use."
+    );
+}
+
+#[test]
+fn test_from_bytes_rejects_bad_magic() {
+    let filter: BloomFilter<String> = BloomFilter::with_hash_count(1000, 3);
+    let mut bytes = filter.to_bytes();
+    bytes[0] = b'X';
+
+    assert!(matches!(
+        BloomFilter::<String>::from_bytes(&bytes),
+        Err(BloomFilterDecodeError::BadMagic)
+    ));
+}
+
+#[test]
+fn test_from_bytes_rejects_truncated_payload() {
+    let filter: BloomFilter<String> = BloomFilter::with_hash_count_pow2(1000, 3);
+    let bytes = filter.to_bytes();
+
+    assert!(matches!(
+        BloomFilter::<String>::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(BloomFilterDecodeError::Truncated)
+    ));
+}
+
+#[test]
+fn test_from_bytes_rejects_huge_word_count_without_allocating() {
+    // No target FPR, so the header is: magic(4) + version(1) + sizing_tag(1)
+    // + sizing_param(8) + hash_count(4) + count(8) + fpr_flag(1) + bit_len(8)
+    // = 35 bytes before the `word_count` field.
+    let filter: BloomFilter<String> = BloomFilter::with_hash_count_pow2(1000, 3);
+    let mut bytes = filter.to_bytes();
+    let word_count_offset = 35;
+    bytes[word_count_offset..word_count_offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+
+    // Declares ~2^64 raw words while the payload actually holds only a
+    // handful more bytes; must fail with `Truncated` rather than attempt an
+    // up-front allocation sized off the untrusted word count.
+    assert!(matches!(
+        BloomFilter::<String>::from_bytes(&bytes),
+        Err(BloomFilterDecodeError::Truncated)
+    ));
+}
+
+#[test]
+fn test_from_bytes_rejects_sizing_mask_inconsistent_with_bit_array() {
+    let filter: BloomFilter<String> = BloomFilter::with_hash_count_pow2(1000, 3);
+    let mut bytes = filter.to_bytes();
+
+    // Header layout (no target FPR): magic(4) + version(1) + sizing_tag(1)
+    // precede the sizing_param(8) field at offset 6. Overwrite it with a
+    // mask implying a far larger bit array than the words that follow
+    // actually encode.
+    let sizing_param_offset = 6;
+    bytes[sizing_param_offset..sizing_param_offset + 8]
+        .copy_from_slice(&(2u64.pow(40) - 1).to_le_bytes());
+
+    assert!(matches!(
+        BloomFilter::<String>::from_bytes(&bytes),
+        Err(BloomFilterDecodeError::InconsistentBitLength)
+    ));
+}
+
+#[test]
+fn test_from_bytes_rejects_pow2_mask_that_would_overflow() {
+    let filter: BloomFilter<String> = BloomFilter::with_hash_count_pow2(1000, 3);
+    let mut bytes = filter.to_bytes();
+
+    // `mask + 1` must not be computed on an untrusted mask without
+    // checking for overflow first: `u64::MAX + 1` would panic.
+    let sizing_param_offset = 6;
+    bytes[sizing_param_offset..sizing_param_offset + 8]
+        .copy_from_slice(&u64::MAX.to_le_bytes());
+
+    assert!(matches!(
+        BloomFilter::<String>::from_bytes(&bytes),
+        Err(BloomFilterDecodeError::InconsistentBitLength)
+    ));
+}
+
+#[test]
+fn test_from_bytes_rejects_zero_size_modulo_filter() {
+    let filter: BloomFilter<String> = BloomFilter::with_hash_count(1000, 3);
+    let mut bytes = filter.to_bytes();
+
+    // A decoded size of 0 would pass the `sizing.bits() == bit_vector.len()`
+    // check trivially (both sides 0) and then panic on the first `add`/
+    // `contains` with a modulo-by-zero in `Sizing::index`.
+    let sizing_param_offset = 6;
+    bytes[sizing_param_offset..sizing_param_offset + 8].copy_from_slice(&0u64.to_le_bytes());
+    let bit_len_offset = 6 + 8 + 4 + 8 + 1;
+    bytes[bit_len_offset..bit_len_offset + 8].copy_from_slice(&0u64.to_le_bytes());
+
+    assert!(matches!(
+        BloomFilter::<String>::from_bytes(&bytes),
+        Err(BloomFilterDecodeError::InconsistentBitLength)
+    ));
+}
+
+fn setup_counting_filter() -> CountingBloomFilter<String, u8> {
+    let size = 10000;
+    let hash_count = 3;
+
+    CountingBloomFilter::with_hash_count(size, hash_count)
+}
+
+#[test]
+fn test_counting_filter_add_and_remove() {
+    let mut filter = setup_counting_filter();
+
+    filter.add(&"item1".to_string());
+    assert!(filter.contains(&"item1".to_string()), "Item added should be present.");
+
+    filter.remove(&"item1".to_string());
+    assert!(
+        !filter.contains(&"item1".to_string()),
+        "Item should be absent after being removed."
+    );
+}
+
+#[test]
+fn test_counting_filter_shared_slots_survive_unrelated_removal() {
+    let mut filter = setup_counting_filter();
+
+    filter.add(&"item1".to_string());
+    filter.add(&"item2".to_string());
+    filter.remove(&"item2".to_string());
+
+    assert!(
+        filter.contains(&"item1".to_string()),
+        "Removing one item should not affect another item's slots being nonzero."
+    );
+}
+
+#[test]
+fn test_counting_filter_decrement_does_not_underflow() {
+    let mut filter: CountingBloomFilter<String, u8> = setup_counting_filter();
+
+    // Removing an item that was never added should saturate at zero
+    // instead of wrapping, and must not panic.
+    filter.remove(&"never-added".to_string());
+    assert!(!filter.contains(&"never-added".to_string()));
+}
+
+#[test]
+fn test_counting_filter_pow2_sizing_rounds_up_and_matches_behavior() {
+    let mut filter: CountingBloomFilter<String, u8> =
+        CountingBloomFilter::with_hash_count_pow2(1000, 3);
+
+    assert!(filter.size().is_power_of_two(), "pow2 sizing should round up to a power of two.");
+    assert!(filter.size() >= 1000);
+
+    filter.add(&"item1".to_string());
+    assert!(filter.contains(&"item1".to_string()));
+    assert!(!filter.contains(&"nonexistent".to_string()));
+}
+}
\ No newline at end of file